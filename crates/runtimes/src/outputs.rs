@@ -1,42 +1,505 @@
+use anyhow::Result;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use gpui::{AnyElement, AppContext, EventEmitter, FontWeight, Image, ImageFormat, Render, View};
+use language::LanguageRegistry;
+use markdown::{Markdown, MarkdownElement, MarkdownStyle};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use runtimelib::{ExecutionState, JupyterMessageContent, MimeType, Stdio};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use settings::{Settings, SettingsSources};
+use std::path::PathBuf;
+use std::sync::Arc;
+use ui::{div, img, prelude::*, v_flex, IntoElement, Styled, ViewContext};
+use unicode_width::UnicodeWidthStr;
+
 use crate::stdio::TerminalOutput;
 use crate::ExecutionId;
-use gpui::{AnyElement, FontWeight, Render, View};
-use runtimelib::{ExecutionState, JupyterMessageContent, MimeType};
-use serde_json::Value;
-use ui::{div, prelude::*, v_flex, IntoElement, Styled, ViewContext};
+
+/// Settings that control how Jupyter cell outputs are displayed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct JupyterOutputSettingsContent {
+    /// Whether to soft-wrap long output lines to the width of the cell.
+    ///
+    /// Turn this off for outputs that read better with a horizontal
+    /// scrollbar, such as wide tables.
+    ///
+    /// Default: true
+    pub wrap_output: Option<bool>,
+    /// How many levels deep an `application/json` output's tree view starts
+    /// expanded. Nodes beyond this depth start collapsed.
+    ///
+    /// Default: 2
+    pub json_tree_expand_depth: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct JupyterOutputSettings {
+    pub wrap_output: bool,
+    pub json_tree_expand_depth: usize,
+}
+
+impl Settings for JupyterOutputSettings {
+    const KEY: Option<&'static str> = Some("jupyter");
+
+    type FileContent = JupyterOutputSettingsContent;
+
+    fn load(sources: SettingsSources<Self::FileContent>, _cx: &mut AppContext) -> Result<Self> {
+        let content: JupyterOutputSettingsContent = sources.json_merge()?;
+        Ok(Self {
+            wrap_output: content.wrap_output.unwrap_or(true),
+            json_tree_expand_depth: content.json_tree_expand_depth.unwrap_or(2),
+        })
+    }
+}
+
+/// Computes the number of visual rows `text` occupies once wrapped to
+/// `columns` columns, using display width (wide CJK glyphs count as two
+/// columns, zero-width combining marks count as zero) rather than byte or
+/// character counts. An empty line still takes up one row.
+fn wrapped_row_count(text: &str, columns: u32) -> u32 {
+    if text.is_empty() {
+        return 1;
+    }
+
+    text.lines()
+        .map(|line| {
+            let width = line.width() as u32;
+            if width == 0 || columns == 0 {
+                1
+            } else {
+                width.div_ceil(columns)
+            }
+        })
+        .sum::<u32>()
+        .max(1)
+}
+
+pub struct ImageView {
+    mimetype: MimeType,
+    image: Arc<Image>,
+    width: Option<u32>,
+    height: Option<u32>,
+    display_id: Option<String>,
+}
+
+impl ImageView {
+    fn from_media(mimetype: &MimeType, value: &Value, metadata: Option<&Value>) -> Option<Self> {
+        let format = match mimetype {
+            MimeType::Png => ImageFormat::Png,
+            MimeType::Jpeg => ImageFormat::Jpeg,
+            MimeType::Svg => ImageFormat::Svg,
+            _ => return None,
+        };
+
+        let bytes = extract_image_bytes(mimetype, value)?;
+        let image = Arc::new(Image::from_bytes(format, bytes));
+
+        let (width, height) = metadata
+            .and_then(|metadata| metadata.get(mimetype.as_str()))
+            .map(|hints| {
+                let width = hints.get("width").and_then(Value::as_u64).map(|w| w as u32);
+                let height = hints
+                    .get("height")
+                    .and_then(Value::as_u64)
+                    .map(|h| h as u32);
+                (width, height)
+            })
+            .unwrap_or((None, None));
+
+        Some(Self {
+            mimetype: mimetype.clone(),
+            image,
+            width,
+            height,
+            display_id: None,
+        })
+    }
+
+    fn render(&self) -> AnyElement {
+        let mut el = img(self.image.clone());
+
+        if let Some(width) = self.width {
+            el = el.w(px(width as f32));
+        }
+        if let Some(height) = self.height {
+            el = el.h(px(height as f32));
+        }
+
+        el.into_any_element()
+    }
+
+    /// Most kernels don't send size metadata (matplotlib/plotly PNGs rarely
+    /// do), so we can't rely on `self.height` alone. Lay out the rendered
+    /// element instead to get the decoded image's actual pixel height.
+    fn num_lines(&self, cx: &mut WindowContext) -> u8 {
+        let line_height = cx.line_height().0;
+        if line_height <= 0.0 {
+            return 1;
+        }
+
+        let mut element = self.render();
+        let size = element.layout_as_root(
+            gpui::size(
+                gpui::AvailableSpace::MinContent,
+                gpui::AvailableSpace::MinContent,
+            ),
+            cx,
+        );
+
+        ((size.height.0 / line_height).ceil() as u32).clamp(1, u8::MAX as u32) as u8
+    }
+}
+
+/// Applies the `wrap_output` setting to a text-bearing element: when
+/// wrapping is disabled the line is left unbroken and the element itself
+/// scrolls horizontally instead (useful for wide tables).
+fn apply_wrap_style<E: Styled>(el: E, wrap: bool) -> E {
+    if wrap {
+        el
+    } else {
+        el.whitespace_nowrap().overflow_x_scroll()
+    }
+}
+
+/// Pulls the raw bytes for an image bundle out of a Jupyter `data` payload.
+///
+/// Most kernels base64-encode raster images, but some send the bytes
+/// straight through as a JSON array of numbers, so handle both.
+fn extract_image_bytes(mimetype: &MimeType, value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::String(s) => match mimetype {
+            MimeType::Svg => Some(s.clone().into_bytes()),
+            _ => BASE64_STANDARD.decode(s).ok(),
+        },
+        Value::Array(bytes) => bytes
+            .iter()
+            .map(|byte| byte.as_u64().map(|byte| byte as u8))
+            .collect(),
+        _ => None,
+    }
+}
 
 pub enum OutputType {
-    Plain(TerminalOutput),
-    Media((MimeType, Value)),
-    Stream(TerminalOutput),
+    Plain {
+        content: TerminalOutput,
+        display_id: Option<String>,
+    },
+    Media {
+        mimetype: MimeType,
+        value: Value,
+        display_id: Option<String>,
+    },
+    Image(ImageView),
+    Markdown {
+        markdown: View<Markdown>,
+        display_id: Option<String>,
+    },
+    Json {
+        root: JsonNode,
+        display_id: Option<String>,
+    },
+    Stream {
+        content: TerminalOutput,
+        stream: Stdio,
+    },
     ErrorOutput {
         ename: String,
         evalue: String,
         traceback: TerminalOutput,
+        /// Structured frames parsed out of the traceback, one per `File "...", line N, in fn`
+        /// entry. Empty when the traceback didn't parse, in which case we fall back to
+        /// rendering `traceback` as plain ANSI-colored text.
+        frames: Vec<TracebackFrame>,
+    },
+}
+
+impl OutputType {
+    /// The `display_id` this output was published under, if any. Used to
+    /// locate the output an `update_display_data` message should replace.
+    fn display_id(&self) -> Option<&str> {
+        match self {
+            Self::Plain { display_id, .. } => display_id.as_deref(),
+            Self::Media { display_id, .. } => display_id.as_deref(),
+            Self::Image(image) => image.display_id.as_deref(),
+            Self::Markdown { display_id, .. } => display_id.as_deref(),
+            Self::Json { display_id, .. } => display_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// A single stack frame parsed out of a Python traceback.
+#[derive(Clone, Debug)]
+pub struct TracebackFrame {
+    pub file: String,
+    pub line: u32,
+    pub function: String,
+    /// The offending source line, when the kernel included one.
+    pub source: Option<String>,
+    /// `(start_column, length)` of the `^^^` caret underline beneath `source`, if present.
+    pub carets: Option<(usize, usize)>,
+    /// Frames start expanded; collapsing one hides its source snippet.
+    pub expanded: bool,
+}
+
+static ANSI_ESCAPE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+
+static TRACEBACK_FRAME_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^\s*File "(?P<file>[^"]+)", line (?P<line>\d+), in (?P<function>.+?)\s*$"#)
+        .unwrap()
+});
+
+fn strip_ansi(line: &str) -> std::borrow::Cow<str> {
+    ANSI_ESCAPE_RE.replace_all(line, "")
+}
+
+/// Parses the `File "path", line N, in function` frames out of a raw Python
+/// traceback, along with the source snippet and caret underline that follow
+/// each frame header, when the kernel included them.
+fn parse_traceback_frames(traceback: &[String]) -> Vec<TracebackFrame> {
+    let lines: Vec<_> = traceback.iter().map(|line| strip_ansi(line)).collect();
+    let mut frames = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let Some(caps) = TRACEBACK_FRAME_RE.captures(&lines[i]) else {
+            i += 1;
+            continue;
+        };
+
+        let file = caps["file"].to_string();
+        let line = caps["line"].parse().unwrap_or(0);
+        let function = caps["function"].to_string();
+        let mut source = None;
+        let mut carets = None;
+
+        if let Some(next) = lines.get(i + 1) {
+            if !next.trim().is_empty() && TRACEBACK_FRAME_RE.captures(next).is_none() {
+                source = Some(next.trim().to_string());
+                i += 1;
+
+                if let Some(caret_line) = lines.get(i + 1) {
+                    let trimmed = caret_line.trim();
+                    if !trimmed.is_empty() && trimmed.chars().all(|c| c == '^' || c == ' ') {
+                        // `source` was trimmed of its leading whitespace above, so the
+                        // caret offset needs the same prefix stripped or it'd point
+                        // past the start of the (now shorter) rendered source line.
+                        let source_indent = next.len() - next.trim_start().len();
+                        let start = caret_line.find('^').unwrap_or(0).saturating_sub(source_indent);
+                        carets = Some((start, trimmed.matches('^').count()));
+                        i += 1;
+                    }
+                }
+            }
+        }
+
+        frames.push(TracebackFrame {
+            file,
+            line,
+            function,
+            source,
+            carets,
+            expanded: true,
+        });
+        i += 1;
+    }
+
+    frames
+}
+
+/// Events emitted by [`ExecutionView`] for things it can't handle on its own,
+/// like jumping to a location referenced by a traceback frame.
+pub enum ExecutionViewEvent {
+    OpenLocation { path: PathBuf, line: u32 },
+}
+
+impl EventEmitter<ExecutionViewEvent> for ExecutionView {}
+
+/// A node in an `application/json` output's collapsible tree view. Container
+/// nodes (`Array`/`Object`) carry their own `expanded` flag so toggling one
+/// doesn't affect its siblings.
+#[derive(Clone, Debug)]
+pub enum JsonNode {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array {
+        children: Vec<JsonNode>,
+        expanded: bool,
+    },
+    Object {
+        children: Vec<(String, JsonNode)>,
+        expanded: bool,
     },
 }
 
+impl JsonNode {
+    /// Builds a tree from `value`, starting nodes shallower than `expand_depth`
+    /// expanded and everything beyond it collapsed.
+    fn from_value(value: &Value, depth: usize, expand_depth: usize) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(b) => Self::Bool(*b),
+            Value::Number(n) => Self::Number(n.to_string()),
+            Value::String(s) => Self::String(s.clone()),
+            Value::Array(items) => Self::Array {
+                children: items
+                    .iter()
+                    .map(|item| Self::from_value(item, depth + 1, expand_depth))
+                    .collect(),
+                expanded: depth < expand_depth,
+            },
+            Value::Object(entries) => Self::Object {
+                children: entries
+                    .iter()
+                    .map(|(key, item)| {
+                        (key.clone(), Self::from_value(item, depth + 1, expand_depth))
+                    })
+                    .collect(),
+                expanded: depth < expand_depth,
+            },
+        }
+    }
+
+    /// Toggles the `expanded` flag of the node reached by following `path`
+    /// (a sequence of child indices) from `self`. No-ops on leaves or an
+    /// out-of-range path.
+    fn toggle(&mut self, path: &[usize]) {
+        let Some((&index, rest)) = path.split_first() else {
+            match self {
+                Self::Array { expanded, .. } | Self::Object { expanded, .. } => {
+                    *expanded = !*expanded
+                }
+                _ => {}
+            }
+            return;
+        };
+
+        match self {
+            Self::Array { children, .. } => {
+                if let Some(child) = children.get_mut(index) {
+                    child.toggle(rest);
+                }
+            }
+            Self::Object { children, .. } => {
+                if let Some((_, child)) = children.get_mut(index) {
+                    child.toggle(rest);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The number of rows this node (and its currently-expanded descendants)
+    /// take up when rendered.
+    fn visible_row_count(&self) -> u32 {
+        match self {
+            Self::Array { children, expanded } => {
+                1 + if *expanded {
+                    children.iter().map(Self::visible_row_count).sum()
+                } else {
+                    0
+                }
+            }
+            Self::Object { children, expanded } => {
+                1 + if *expanded {
+                    children
+                        .iter()
+                        .map(|(_, child)| child.visible_row_count())
+                        .sum()
+                } else {
+                    0
+                }
+            }
+            _ => 1,
+        }
+    }
+}
+
+fn markdown_style(cx: &WindowContext) -> MarkdownStyle {
+    let theme = cx.theme();
+    let buffer_font = theme::ThemeSettings::get_global(cx).buffer_font.clone();
+
+    MarkdownStyle {
+        base_text_style: cx.text_style(),
+        code_block: gpui::StyleRefinement::default()
+            .font_family(buffer_font.family)
+            .background_color(theme.colors().editor_background),
+        inline_code: gpui::TextStyleRefinement {
+            font_family: Some(buffer_font.family),
+            background_color: Some(theme.colors().editor_background),
+            ..Default::default()
+        },
+        rule_color: theme.colors().border,
+        block_quote_border_color: theme.colors().border,
+        block_quote: gpui::TextStyleRefinement {
+            color: Some(theme.colors().text_muted),
+            ..Default::default()
+        },
+        link: gpui::TextStyleRefinement {
+            color: Some(theme.colors().text_accent),
+            underline: Some(gpui::UnderlineStyle {
+                thickness: px(1.),
+                color: Some(theme.colors().text_accent),
+                wavy: false,
+            }),
+            ..Default::default()
+        },
+        syntax: theme.syntax().clone(),
+        selection_background_color: theme.players().local().selection,
+        ..Default::default()
+    }
+}
+
 pub trait LineHeight: Sized {
-    fn num_lines(&self, cx: &mut WindowContext) -> u8;
+    /// `columns` is the number of character columns available to the cell;
+    /// long lines wrap to additional rows within that width.
+    fn num_lines(&self, columns: u32, cx: &mut WindowContext) -> u8;
 }
 
 // Priority order goes from highest to lowest (plaintext is the common fallback)
-const PRIORITY_ORDER: &[MimeType] = &[MimeType::Markdown, MimeType::Plain];
+const PRIORITY_ORDER: &[MimeType] = &[
+    MimeType::Png,
+    MimeType::Jpeg,
+    MimeType::Svg,
+    MimeType::Markdown,
+    MimeType::Json,
+    MimeType::Plain,
+];
 
 impl OutputType {
-    fn render(&self, cx: &ViewContext<ExecutionView>) -> Option<AnyElement> {
+    fn render(&self, output_index: usize, cx: &ViewContext<ExecutionView>) -> Option<AnyElement> {
+        let wrap_output = JupyterOutputSettings::get_global(cx).wrap_output;
+
         let el = match self {
             // Note: in typical frontends we would show the execute_result.execution_count
             // Here we can just handle either
-            Self::Plain(stdio) => Some(stdio.render(cx)),
-            // Self::Markdown(markdown) => Some(markdown.render(theme)),
-            Self::Media((mimetype, value)) => render_rich(mimetype, value),
-            Self::Stream(stdio) => Some(stdio.render(cx)),
+            Self::Plain { content, .. } => Some(content.render(cx)),
+            Self::Media {
+                mimetype, value, ..
+            } => render_rich(mimetype, value, None, wrap_output),
+            Self::Image(image) => Some(image.render()),
+            Self::Markdown { markdown, .. } => Some(render_markdown(markdown, cx)),
+            Self::Json { root, .. } => Some(render_json_node(root, output_index, &[], 0, cx)),
+            Self::Stream { content, stream } => Some(render_stream(content, stream, cx)),
             Self::ErrorOutput {
                 ename,
                 evalue,
                 traceback,
-            } => render_error_output(ename, evalue, traceback, cx),
+                frames,
+            } => Some(render_error_output(
+                ename,
+                evalue,
+                traceback,
+                frames,
+                output_index,
+                wrap_output,
+                cx,
+            )),
         };
 
         el
@@ -44,69 +507,379 @@ impl OutputType {
 }
 
 impl LineHeight for OutputType {
-    /// Calculates the expected number of lines
-    fn num_lines(&self, cx: &mut WindowContext) -> u8 {
+    /// Calculates the expected number of lines, wrapping long lines to `columns`
+    fn num_lines(&self, columns: u32, cx: &mut WindowContext) -> u8 {
+        let wrap_output = JupyterOutputSettings::get_global(cx).wrap_output;
+        let columns = if wrap_output { columns } else { u32::MAX };
+
         match self {
-            Self::Plain(stdio) => stdio.num_lines(cx),
-            Self::Media((_mimetype, value)) => value.as_str().unwrap_or("").lines().count() as u8,
-            Self::Stream(stdio) => stdio.num_lines(cx),
+            Self::Plain { content, .. } => content.num_lines(columns, cx),
+            Self::Media { value, .. } => {
+                wrapped_row_count(value.as_str().unwrap_or(""), columns).min(u8::MAX as u32) as u8
+            }
+            Self::Image(image) => image.num_lines(cx),
+            Self::Markdown { markdown, .. } => markdown_num_lines(markdown, columns, cx),
+            Self::Json { root, .. } => root.visible_row_count().min(u8::MAX as u32) as u8,
+            Self::Stream { content, .. } => content.num_lines(columns, cx),
             Self::ErrorOutput {
                 ename,
                 evalue,
                 traceback,
+                frames,
             } => {
-                let mut height: u8 = 0;
-                height = height.saturating_add(ename.lines().count() as u8);
-                height = height.saturating_add(evalue.lines().count() as u8);
-                height = height.saturating_add(traceback.num_lines(cx));
-                height
+                let mut height: u32 = 0;
+                height += wrapped_row_count(ename, columns);
+                height += wrapped_row_count(evalue, columns);
+
+                if frames.is_empty() {
+                    height += traceback.num_lines(columns, cx) as u32;
+                } else {
+                    for frame in frames {
+                        height += 1; // the "file:line in function" header is always shown
+                        if frame.expanded && frame.source.is_some() {
+                            height += 1; // the source snippet
+                            if frame.carets.is_some() {
+                                height += 1; // the caret underline
+                            }
+                        }
+                    }
+                }
+
+                height.min(u8::MAX as u32) as u8
             }
         }
     }
 }
 
-fn render_rich(mimetype: &MimeType, value: &Value) -> Option<AnyElement> {
+fn render_rich(
+    mimetype: &MimeType,
+    value: &Value,
+    metadata: Option<&Value>,
+    wrap_output: bool,
+) -> Option<AnyElement> {
     // TODO: Make the media types be enums that contain their values to make this more readable
     match mimetype {
         MimeType::Plain => Some(
-            div()
-                .child(value.as_str().unwrap_or("").to_string())
-                .into_any_element(),
-        ),
-        MimeType::Markdown => Some(
-            div()
+            apply_wrap_style(div(), wrap_output)
                 .child(value.as_str().unwrap_or("").to_string())
                 .into_any_element(),
         ),
+        MimeType::Png | MimeType::Jpeg | MimeType::Svg => {
+            ImageView::from_media(mimetype, value, metadata).map(|image| image.render())
+        }
         _ => None,
     }
 }
 
+fn render_markdown(markdown: &View<Markdown>, cx: &ViewContext<ExecutionView>) -> AnyElement {
+    div()
+        .w_full()
+        .child(MarkdownElement::new(markdown.clone(), markdown_style(cx)))
+        .into_any_element()
+}
+
+/// Monospace glyphs are roughly half as wide as they are tall; used to turn
+/// a character-column count into an approximate pixel width.
+const MONOSPACE_ASPECT_RATIO: f32 = 0.5;
+
+/// Markdown is rendered as a block tree, so its line count depends on how
+/// headings, lists, and wrapped paragraphs lay out, not on the raw source's
+/// newline count. We measure the rendered element itself, laid out to
+/// `columns` (the same cell width every other `num_lines` arm wraps to)
+/// rather than the full window, to account for that.
+fn markdown_num_lines(markdown: &View<Markdown>, columns: u32, cx: &mut WindowContext) -> u8 {
+    let line_height = cx.line_height().0;
+    if line_height <= 0.0 {
+        return 1;
+    }
+
+    let mut element = div()
+        .w_full()
+        .child(MarkdownElement::new(markdown.clone(), markdown_style(cx)))
+        .into_any_element();
+
+    let available_width = if columns == 0 || columns == u32::MAX {
+        gpui::AvailableSpace::MinContent
+    } else {
+        gpui::AvailableSpace::Definite(px(columns as f32 * line_height * MONOSPACE_ASPECT_RATIO))
+    };
+
+    let size = element.layout_as_root(
+        gpui::size(available_width, gpui::AvailableSpace::MinContent),
+        cx,
+    );
+
+    ((size.height.0 / line_height).ceil() as u32).clamp(1, u8::MAX as u32) as u8
+}
+
+fn render_stream(
+    content: &TerminalOutput,
+    stream: &Stdio,
+    cx: &ViewContext<ExecutionView>,
+) -> AnyElement {
+    match stream {
+        Stdio::Stdout => content.render(cx),
+        Stdio::Stderr => {
+            let theme = cx.theme();
+
+            v_flex()
+                .w_full()
+                .bg(theme.status().error_background)
+                .border_l_1()
+                .border_color(theme.status().error_border)
+                .child(content.render(cx))
+                .into_any_element()
+        }
+    }
+}
+
 fn render_error_output(
     ename: &String,
     evalue: &String,
     traceback: &TerminalOutput,
+    frames: &[TracebackFrame],
+    output_index: usize,
+    wrap_output: bool,
     cx: &ViewContext<ExecutionView>,
-) -> Option<AnyElement> {
+) -> AnyElement {
     let theme = cx.theme();
-
     let colors = cx.theme().colors();
 
-    Some(
+    let body = if frames.is_empty() {
+        // Frame parsing failed (or the kernel isn't Python); fall back to the
+        // raw ANSI-colored traceback.
+        traceback.render(cx).into_any_element()
+    } else {
         v_flex()
             .w_full()
-            .bg(colors.background)
-            .p_4()
-            .border_l_1()
-            .border_color(theme.status().error_border)
-            .child(
-                h_flex()
-                    .font_weight(FontWeight::BOLD)
-                    .child(format!("{}: {}", ename, evalue)),
-            )
-            .child(traceback.render(cx))
-            .into_any_element(),
-    )
+            .gap_1()
+            .children(frames.iter().enumerate().map(|(frame_index, frame)| {
+                render_traceback_frame(frame, output_index, frame_index, cx)
+            }))
+            .into_any_element()
+    };
+
+    v_flex()
+        .w_full()
+        .bg(colors.background)
+        .p_4()
+        .border_l_1()
+        .border_color(theme.status().error_border)
+        .child(
+            apply_wrap_style(h_flex(), wrap_output)
+                .font_weight(FontWeight::BOLD)
+                .child(format!("{}: {}", ename, evalue)),
+        )
+        .child(body)
+        .into_any_element()
+}
+
+fn render_traceback_frame(
+    frame: &TracebackFrame,
+    output_index: usize,
+    frame_index: usize,
+    cx: &ViewContext<ExecutionView>,
+) -> AnyElement {
+    let theme = cx.theme();
+    let colors = theme.colors();
+
+    let location = format!("{}:{}", frame.file, frame.line);
+    let path = PathBuf::from(frame.file.clone());
+    let line = frame.line;
+
+    let header = h_flex()
+        .id(("traceback-frame", frame_index))
+        .w_full()
+        .gap_1()
+        .cursor_pointer()
+        .on_click(cx.listener(move |view, _event, cx| {
+            if let Some(OutputType::ErrorOutput { frames, .. }) = view.outputs.get_mut(output_index)
+            {
+                if let Some(frame) = frames.get_mut(frame_index) {
+                    frame.expanded = !frame.expanded;
+                }
+            }
+            cx.notify();
+        }))
+        .child(Icon::new(if frame.expanded {
+            IconName::ChevronDown
+        } else {
+            IconName::ChevronRight
+        }))
+        .child(
+            div()
+                .id(("traceback-location", frame_index))
+                .text_color(colors.text_accent)
+                .cursor_pointer()
+                .on_click(cx.listener(move |_view, _event, cx| {
+                    cx.stop_propagation();
+                    cx.emit(ExecutionViewEvent::OpenLocation {
+                        path: path.clone(),
+                        line,
+                    });
+                }))
+                .child(location),
+        )
+        .child(
+            div()
+                .text_color(colors.text_muted)
+                .child(format!("in {}", frame.function)),
+        );
+
+    let mut frame_el = v_flex().w_full().child(header);
+
+    if frame.expanded {
+        if let Some(source) = &frame.source {
+            frame_el = frame_el.child(div().pl_6().font_family("Zed Mono").child(source.clone()));
+
+            if let Some((start, len)) = frame.carets {
+                let underline = " ".repeat(start) + &"^".repeat(len.max(1));
+                frame_el = frame_el.child(
+                    div()
+                        .pl_6()
+                        .font_family("Zed Mono")
+                        .text_color(theme.status().error)
+                        .child(underline),
+                );
+            }
+        }
+    }
+
+    frame_el.into_any_element()
+}
+
+fn render_json_leaf(node: &JsonNode) -> String {
+    match node {
+        JsonNode::Null => "null".to_string(),
+        JsonNode::Bool(b) => b.to_string(),
+        JsonNode::Number(n) => n.clone(),
+        JsonNode::String(s) => format!("{:?}", s),
+        JsonNode::Array { children, .. } => format!("[{} items]", children.len()),
+        JsonNode::Object { children, .. } => format!("{{{} keys}}", children.len()),
+    }
+}
+
+/// Renders one node of an `application/json` output's tree view. `path` is
+/// the sequence of child indices from the root to this node, and doubles as
+/// the node's element id and the argument to [`JsonNode::toggle`].
+fn render_json_node(
+    node: &JsonNode,
+    output_index: usize,
+    path: &[usize],
+    depth: usize,
+    cx: &ViewContext<ExecutionView>,
+) -> AnyElement {
+    let colors = cx.theme().colors();
+    // Seed with a nonzero salt and fold `output_index` in as its own digit so
+    // the root (empty `path`) never collides with one of its descendants,
+    // which `acc * 31 + index` alone would do whenever `output_index == 0`.
+    let node_id = path.iter().fold(
+        (output_index as u64).wrapping_add(1) * 1_000_003,
+        |acc, index| acc * 31 + *index as u64 + 1,
+    );
+
+    let (children, expanded): (Option<Vec<AnyElement>>, bool) = match node {
+        JsonNode::Array { children, expanded } => {
+            let rendered = if *expanded {
+                Some(
+                    children
+                        .iter()
+                        .enumerate()
+                        .map(|(index, child)| {
+                            let mut child_path = path.to_vec();
+                            child_path.push(index);
+                            v_flex()
+                                .pl_4()
+                                .child(
+                                    h_flex()
+                                        .child(
+                                            div()
+                                                .text_color(colors.text_muted)
+                                                .child(format!("{index}:")),
+                                        )
+                                        .child(render_json_node(
+                                            child,
+                                            output_index,
+                                            &child_path,
+                                            depth + 1,
+                                            cx,
+                                        )),
+                                )
+                                .into_any_element()
+                        })
+                        .collect(),
+                )
+            } else {
+                None
+            };
+            (rendered, *expanded)
+        }
+        JsonNode::Object { children, expanded } => {
+            let rendered = if *expanded {
+                Some(
+                    children
+                        .iter()
+                        .enumerate()
+                        .map(|(index, (key, child))| {
+                            let mut child_path = path.to_vec();
+                            child_path.push(index);
+                            v_flex()
+                                .pl_4()
+                                .child(
+                                    h_flex()
+                                        .child(
+                                            div()
+                                                .text_color(colors.text_muted)
+                                                .child(format!("{key}:")),
+                                        )
+                                        .child(render_json_node(
+                                            child,
+                                            output_index,
+                                            &child_path,
+                                            depth + 1,
+                                            cx,
+                                        )),
+                                )
+                                .into_any_element()
+                        })
+                        .collect(),
+                )
+            } else {
+                None
+            };
+            (rendered, *expanded)
+        }
+        _ => (None, false),
+    };
+
+    let is_container = matches!(node, JsonNode::Array { .. } | JsonNode::Object { .. });
+
+    let mut row = h_flex().id(("json-node", node_id)).gap_1();
+
+    if is_container {
+        let path = path.to_vec();
+        row = row
+            .cursor_pointer()
+            .on_click(cx.listener(move |view, _event, cx| {
+                if let Some(OutputType::Json { root, .. }) = view.outputs.get_mut(output_index) {
+                    root.toggle(&path);
+                }
+                cx.notify();
+            }))
+            .child(Icon::new(if expanded {
+                IconName::ChevronDown
+            } else {
+                IconName::ChevronRight
+            }))
+            .child(div().child(render_json_leaf(node)));
+    } else {
+        row = row.child(div().child(render_json_leaf(node)));
+    }
+
+    match children {
+        Some(children) => v_flex().child(row).children(children).into_any_element(),
+        None => row.into_any_element(),
+    }
 }
 
 #[derive(Default)]
@@ -126,19 +899,100 @@ pub struct ExecutionView {
     pub execution_id: ExecutionId,
     pub outputs: Vec<OutputType>,
     pub status: ExecutionStatus,
+    language_registry: Arc<LanguageRegistry>,
+    /// Set when a `clear_output` arrives with `wait: true`; the actual clear
+    /// is deferred until the next output lands, so the cell doesn't blank out
+    /// before the replacement is ready.
+    clear_outputs_on_next_output: bool,
 }
 
 impl ExecutionView {
-    pub fn new(execution_id: ExecutionId, _cx: &mut ViewContext<Self>) -> Self {
+    pub fn new(
+        execution_id: ExecutionId,
+        language_registry: Arc<LanguageRegistry>,
+        _cx: &mut ViewContext<Self>,
+    ) -> Self {
         Self {
             execution_id,
             outputs: Default::default(),
             status: ExecutionStatus::Unknown,
+            language_registry,
+            clear_outputs_on_next_output: false,
+        }
+    }
+
+    fn render_markdown(&self, source: String, cx: &mut ViewContext<Self>) -> View<Markdown> {
+        cx.new_view(|cx| Markdown::new(source, self.language_registry.clone(), None, cx))
+    }
+
+    /// Builds the `OutputType` for a rich data bundle (`execute_result` /
+    /// `display_data` / `update_display_data`), tagging it with `display_id`
+    /// so a later `update_display_data` can find and replace it.
+    fn build_rich_output(
+        &mut self,
+        mimetype: MimeType,
+        value: Value,
+        metadata: Option<&Value>,
+        display_id: Option<String>,
+        cx: &mut ViewContext<Self>,
+    ) -> OutputType {
+        match mimetype {
+            MimeType::Plain => OutputType::Plain {
+                content: TerminalOutput::from(value.as_str().unwrap_or("")),
+                display_id,
+            },
+            MimeType::Markdown => {
+                let source = value.as_str().unwrap_or("").to_string();
+                OutputType::Markdown {
+                    markdown: self.render_markdown(source, cx),
+                    display_id,
+                }
+            }
+            MimeType::Json => {
+                let expand_depth = JupyterOutputSettings::get_global(cx).json_tree_expand_depth;
+                OutputType::Json {
+                    root: JsonNode::from_value(&value, 0, expand_depth),
+                    display_id,
+                }
+            }
+            MimeType::Png | MimeType::Jpeg | MimeType::Svg => {
+                match ImageView::from_media(&mimetype, &value, metadata) {
+                    Some(mut image) => {
+                        image.display_id = display_id;
+                        OutputType::Image(image)
+                    }
+                    None => OutputType::Media {
+                        mimetype,
+                        value,
+                        display_id,
+                    },
+                }
+            }
+            _ => OutputType::Media {
+                mimetype,
+                value,
+                display_id,
+            },
         }
     }
 
     /// Accept a Jupyter message belonging to this execution
     pub fn push_message(&mut self, message: &JupyterMessageContent, cx: &mut ViewContext<Self>) {
+        // A deferred `clear_output(wait=true)` is honored as soon as the next
+        // actual output arrives, whichever shape it takes, so a merging stream
+        // chunk or an in-place update doesn't land on top of stale output.
+        let produces_output = matches!(
+            message,
+            JupyterMessageContent::ExecuteResult(_)
+                | JupyterMessageContent::DisplayData(_)
+                | JupyterMessageContent::UpdateDisplayData(_)
+                | JupyterMessageContent::StreamContent(_)
+                | JupyterMessageContent::ErrorOutput(_)
+        );
+        if produces_output && std::mem::take(&mut self.clear_outputs_on_next_output) {
+            self.outputs.clear();
+        }
+
         let output = match message {
             JupyterMessageContent::ExecuteResult(result) => {
                 let (mimetype, value) =
@@ -149,16 +1003,7 @@ impl ExecutionView {
                         return;
                     };
 
-                match mimetype {
-                    MimeType::Plain => {
-                        OutputType::Plain(TerminalOutput::from(value.as_str().unwrap_or("")))
-                    }
-                    MimeType::Markdown => {
-                        OutputType::Plain(TerminalOutput::from(value.as_str().unwrap_or("")))
-                    }
-                    // We don't handle this type, but ok
-                    _ => OutputType::Media((mimetype, value)),
-                }
+                self.build_rich_output(mimetype, value, Some(&result.metadata), None, cx)
             }
             JupyterMessageContent::DisplayData(result) => {
                 let (mimetype, value) =
@@ -169,12 +1014,48 @@ impl ExecutionView {
                         return;
                     };
 
-                OutputType::Media((mimetype, value))
+                let display_id = result.transient.display_id.clone();
+                self.build_rich_output(mimetype, value, Some(&result.metadata), display_id, cx)
+            }
+            JupyterMessageContent::UpdateDisplayData(result) => {
+                let Some(display_id) = result.transient.display_id.clone() else {
+                    // Nothing to match against, so there's nothing we can update
+                    return;
+                };
+
+                let (mimetype, value) =
+                    if let Some((mimetype, value)) = result.data.richest(PRIORITY_ORDER) {
+                        (mimetype, value)
+                    } else {
+                        return;
+                    };
+
+                let output = self.build_rich_output(
+                    mimetype,
+                    value,
+                    Some(&result.metadata),
+                    Some(display_id.clone()),
+                    cx,
+                );
+
+                if let Some(existing) = self
+                    .outputs
+                    .iter_mut()
+                    .find(|output| output.display_id() == Some(display_id.as_str()))
+                {
+                    *existing = output;
+                } else {
+                    self.outputs.push(output);
+                }
+
+                cx.notify();
+                return;
             }
             JupyterMessageContent::StreamContent(result) => {
-                // Previous stream data will combine together, handling colors, carriage returns, etc
-                if let Some(new_terminal) = self.apply_terminal_text(&result.text) {
-                    new_terminal
+                // Previous stream data will combine together, handling colors, carriage returns, etc,
+                // as long as it came from the same stream (stdout doesn't merge into stderr)
+                if let Some(new_stream) = self.apply_terminal_text(&result.text, result.name) {
+                    new_stream
                 } else {
                     return;
                 }
@@ -187,7 +1068,19 @@ impl ExecutionView {
                     ename: result.ename.clone(),
                     evalue: result.evalue.clone(),
                     traceback: terminal,
+                    frames: parse_traceback_frames(&result.traceback),
+                }
+            }
+            JupyterMessageContent::ClearOutput(result) => {
+                if result.wait {
+                    // Defer clearing until the next output actually arrives, so the
+                    // cell doesn't flash empty while the kernel prepares the next one
+                    self.clear_outputs_on_next_output = true;
+                } else {
+                    self.outputs.clear();
                 }
+                cx.notify();
+                return;
             }
             JupyterMessageContent::Status(status) => {
                 match status.execution_state {
@@ -209,12 +1102,19 @@ impl ExecutionView {
         cx.notify();
     }
 
-    fn apply_terminal_text(&mut self, text: &str) -> Option<OutputType> {
+    fn apply_terminal_text(&mut self, text: &str, stream: Stdio) -> Option<OutputType> {
         if let Some(last_output) = self.outputs.last_mut() {
-            if let OutputType::Stream(last_stream) = last_output {
-                last_stream.append_text(text);
-                // Don't need to add a new output, we already have a terminal output
-                return None;
+            if let OutputType::Stream {
+                content: last_stream,
+                stream: last_stream_name,
+            } = last_output
+            {
+                if *last_stream_name == stream {
+                    last_stream.append_text(text);
+                    // Don't need to add a new output, we already have a terminal output
+                    return None;
+                }
+                // stdout and stderr don't interleave into the same block
             }
             // A different output type is "in the way", so we need to create a new output,
             // which is the same as having no prior output
@@ -222,7 +1122,10 @@ impl ExecutionView {
 
         let mut new_terminal = TerminalOutput::new();
         new_terminal.append_text(text);
-        Some(OutputType::Stream(new_terminal))
+        Some(OutputType::Stream {
+            content: new_terminal,
+            stream,
+        })
     }
 
     pub fn set_status(&mut self, status: ExecutionStatus, cx: &mut ViewContext<Self>) {
@@ -251,20 +1154,25 @@ impl Render for ExecutionView {
 
         div()
             .w_full()
-            .children(self.outputs.iter().filter_map(|output| output.render(cx)))
+            .children(
+                self.outputs
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, output)| output.render(index, cx)),
+            )
             .into_any_element()
     }
 }
 
 impl LineHeight for ExecutionView {
-    fn num_lines(&self, cx: &mut WindowContext) -> u8 {
+    fn num_lines(&self, columns: u32, cx: &mut WindowContext) -> u8 {
         if self.outputs.is_empty() {
             return 1; // For the status message if outputs are not there
         }
 
         self.outputs
             .iter()
-            .map(|output| output.num_lines(cx))
+            .map(|output| output.num_lines(columns, cx))
             .fold(0, |acc, additional_height| {
                 acc.saturating_add(additional_height)
             })
@@ -272,7 +1180,9 @@ impl LineHeight for ExecutionView {
 }
 
 impl LineHeight for View<ExecutionView> {
-    fn num_lines(&self, cx: &mut WindowContext) -> u8 {
-        self.update(cx, |execution_view, cx| execution_view.num_lines(cx))
+    fn num_lines(&self, columns: u32, cx: &mut WindowContext) -> u8 {
+        self.update(cx, |execution_view, cx| {
+            execution_view.num_lines(columns, cx)
+        })
     }
-}
\ No newline at end of file
+}